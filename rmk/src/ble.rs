@@ -1,27 +1,42 @@
 pub(crate) mod advertise;
+mod battery;
 mod battery_service;
 pub(crate) mod bonder;
+pub(crate) mod central;
 pub(crate) mod descriptor;
 mod device_information_service;
 mod hid_service;
+pub(crate) mod l2cap;
+pub(crate) mod power;
 pub(crate) mod server;
 pub(crate) mod spec;
 
 use self::{bonder::FlashOperationMessage, server::BleServer};
 use crate::{
-    ble::bonder::{BondInfo, FLASH_CHANNEL},
+    ble::{
+        battery::{BatteryConfig, BatteryMonitor},
+        bonder::{BondInfo, FLASH_CHANNEL},
+        power::{IdlePowerConfig, ACTIVITY_SIGNAL},
+    },
     hid::HidWriterWrapper,
     keyboard::Keyboard,
 };
-use core::{convert::Infallible, mem, ops::Range};
-use defmt::info;
-use embassy_time::Timer;
+use core::{
+    convert::Infallible,
+    mem,
+    ops::Range,
+    sync::atomic::{AtomicU8, Ordering},
+};
+use defmt::{info, warn};
+use embassy_nrf::saadc::Saadc;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embassy_time::{Instant, Timer};
 use embedded_hal::digital::{InputPin, OutputPin};
 use embedded_storage::nor_flash::NorFlash;
 use nrf_softdevice::{ble::Connection, raw, Config, Flash};
 use sequential_storage::{
     cache::NoCache,
-    map::{remove_item, store_item},
+    map::{fetch_item, remove_item, store_item},
 };
 
 /// Flash range which used to save bonding info
@@ -78,6 +93,7 @@ pub(crate) async fn softdevice_task(sd: &'static nrf_softdevice::Softdevice) ->
 #[embassy_executor::task]
 pub(crate) async fn flash_task(f: &'static mut Flash) -> ! {
     let mut storage_data_buffer = [0_u8; 128];
+    restore_active_host_slot(f).await;
     loop {
         let info: FlashOperationMessage = FLASH_CHANNEL.receive().await;
         match info {
@@ -101,16 +117,138 @@ pub(crate) async fn flash_task(f: &'static mut Flash) -> ! {
                     CONFIG_FLASH_RANGE,
                     NoCache::new(),
                     &mut storage_data_buffer,
+                    &b.slot_num,
                     &b,
                 )
                 .await
                 .ok();
             }
+            FlashOperationMessage::ActiveSlot(slot) => {
+                info!("Persisting active host slot: {}", slot);
+
+                // Keyed by the reserved `ACTIVE_SLOT_FLASH_KEY`, not `slot` itself, so
+                // this single entry never collides with a `BondInfo` slot entry.
+                store_item::<u8, _>(
+                    f,
+                    CONFIG_FLASH_RANGE,
+                    NoCache::new(),
+                    &mut storage_data_buffer,
+                    &ACTIVE_SLOT_FLASH_KEY,
+                    &slot,
+                )
+                .await
+                .ok();
+            }
         };
     }
 }
 
-/// BLE keyboard task, run the keyboard with the ble server
+/// Reads back the active host slot persisted by a prior [`switch_to_host`] call, if
+/// any, and stores it into [`ACTIVE_HOST_SLOT`] so [`active_host_slot`] reflects it
+/// immediately on boot instead of defaulting to slot 0.
+pub(crate) async fn restore_active_host_slot(f: &mut Flash) {
+    let mut buffer = [0_u8; 128];
+    if let Ok(Some(slot)) = fetch_item::<u8, _>(
+        f,
+        CONFIG_FLASH_RANGE,
+        NoCache::new(),
+        &mut buffer,
+        &ACTIVE_SLOT_FLASH_KEY,
+    )
+    .await
+    {
+        info!("Restored active host slot from flash: {}", slot);
+        ACTIVE_HOST_SLOT.store(slot, Ordering::Relaxed);
+    }
+}
+
+/// Reserved `sequential_storage` key used to persist the currently active host slot,
+/// chosen outside the `0..BONDED_DEVICE_NUM` range so it never collides with a
+/// [`BondInfo`] slot entry.
+const ACTIVE_SLOT_FLASH_KEY: u8 = 0xFF;
+
+/// Slot number of the host this keyboard is currently connected (or advertising) to.
+/// Updated by [`switch_to_host`] and read back by advertising/reconnect logic on boot.
+static ACTIVE_HOST_SLOT: AtomicU8 = AtomicU8::new(0);
+
+/// Tears down the current connection (if any) and re-advertises directed at the bond
+/// stored in `slot`, so the keyboard reconnects to that specific host instead of
+/// whichever central happens to scan first. Also records `slot` as the active host so
+/// it's restored the next time the keyboard boots. Returns the new `Connection` once a
+/// host reconnects - the caller must hold onto it, since dropping it disconnects again.
+pub(crate) async fn switch_to_host(
+    slot: u8,
+    current_conn: Option<Connection>,
+    bonds: &[Option<BondInfo>; BONDED_DEVICE_NUM],
+) -> Result<Connection, nrf_softdevice::ble::AdvertiseError> {
+    if let Some(conn) = current_conn {
+        conn.disconnect();
+    }
+
+    ACTIVE_HOST_SLOT.store(slot, Ordering::Relaxed);
+    FLASH_CHANNEL
+        .send(FlashOperationMessage::ActiveSlot(slot))
+        .await;
+
+    match bonds.get(slot as usize).and_then(|b| b.as_ref()) {
+        Some(bond) => {
+            info!("Switching to host in slot {}", slot);
+            advertise::start_directed_advertising(bond).await
+        }
+        None => {
+            info!("No bond in slot {}, falling back to undirected advertising", slot);
+            advertise::start_undirected_advertising().await
+        }
+    }
+}
+
+/// Erases the bond stored in `slot`, e.g. in response to a "clear current bond" keycode.
+pub(crate) async fn clear_bond(slot: u8) {
+    FLASH_CHANNEL.send(FlashOperationMessage::Clear(slot)).await;
+}
+
+/// Host-switching action a keycode can emit, decoupled from `Keyboard`'s keycode
+/// handling the same way [`FlashOperationMessage`] decouples flash writes.
+#[derive(Clone, Copy)]
+pub(crate) enum HostSwitchAction {
+    /// Switch to the bonded host in this slot.
+    SwitchHost(u8),
+    /// Clear the bond in the currently active slot.
+    ClearCurrentBond,
+}
+
+/// `Keyboard` sends into this channel when it decodes a host-switch or clear-bond
+/// keycode. Drained by [`keyboard_ble_task`] without blocking, one action per loop.
+pub(crate) static HOST_ACTION_CHANNEL: Channel<ThreadModeRawMutex, HostSwitchAction, 4> =
+    Channel::new();
+
+/// Slot number that should be (re)connected to on boot, as last set by [`switch_to_host`].
+pub(crate) fn active_host_slot() -> u8 {
+    ACTIVE_HOST_SLOT.load(Ordering::Relaxed)
+}
+
+/// Idle state tracked by [`keyboard_ble_task`]'s power-management loop.
+enum IdleState {
+    /// Full-rate scanning, full-rate (tight) connection parameters.
+    Active,
+    /// Still connected, but `idle_config.idle_timeout` has elapsed since the last key
+    /// transition: connection parameters are relaxed to cut radio-on time.
+    ConnectedIdle,
+    /// Connection dropped and advertising stopped after a further idle period; matrix
+    /// scanning has backed off to `idle_config.wake_scan_interval`.
+    Disconnected,
+}
+
+/// BLE keyboard task, run the keyboard with the ble server.
+///
+/// Tracks key activity against `idle_config`: after `idle_config.idle_timeout` without a
+/// transition the connection is relaxed (still connected); after
+/// `idle_config.disconnect_timeout` more it's dropped entirely and advertising stops,
+/// with matrix scanning backed off to `idle_config.wake_scan_interval` until the next
+/// keypress resumes full-rate scanning and re-advertises. Also drains
+/// [`HOST_ACTION_CHANNEL`], switching hosts or clearing the active bond on request.
+/// Returns the new `Connection` once either happens, so the caller can start another
+/// `keyboard_ble_task` run with it.
 pub(crate) async fn keyboard_ble_task<
     'a,
     W: HidWriterWrapper,
@@ -130,25 +268,191 @@ pub(crate) async fn keyboard_ble_task<
     ble_media_writer: &mut W2,
     ble_system_control_writer: &mut W3,
     ble_mouse_writer: &mut W4,
-) {
+    conn: &Connection,
+    bonds: &[Option<BondInfo>; BONDED_DEVICE_NUM],
+    idle_config: &IdlePowerConfig,
+) -> Connection {
     // Wait 2 seconds, ensure that gatt server has been started
     Timer::after_secs(2).await;
+    power::tighten_conn_params(conn, idle_config);
+    let mut state = IdleState::Active;
+    let mut last_activity = Instant::now();
     loop {
-        let _ = keyboard.scan_matrix().await;
-        keyboard.send_keyboard_report(ble_keyboard_writer).await;
-        keyboard.send_media_report(ble_media_writer).await;
-        keyboard.send_system_control_report(ble_system_control_writer).await;
-        keyboard.send_mouse_report(ble_mouse_writer).await;
+        if let Ok(action) = HOST_ACTION_CHANNEL.try_receive() {
+            match action {
+                HostSwitchAction::SwitchHost(slot) => {
+                    info!("Host switch requested, slot {}", slot);
+                    match switch_to_host(slot, Some(conn.clone()), bonds).await {
+                        Ok(new_conn) => return new_conn,
+                        Err(e) => warn!("Host switch failed: {}", e),
+                    }
+                }
+                HostSwitchAction::ClearCurrentBond => {
+                    let slot = active_host_slot();
+                    info!("Clearing bond in active slot {}", slot);
+                    clear_bond(slot).await;
+                }
+            }
+        }
+
+        let active = keyboard.scan_matrix().await;
+        if active {
+            last_activity = Instant::now();
+            ACTIVITY_SIGNAL.signal(());
+            match state {
+                IdleState::Disconnected => {
+                    // The connection was already dropped, so the only thing left to do
+                    // is re-advertise and hand the fresh connection back to the caller.
+                    info!("Key activity detected, restarting advertising");
+                    loop {
+                        match advertise::start_undirected_advertising().await {
+                            Ok(new_conn) => return new_conn,
+                            Err(e) => warn!("Failed to restart advertising: {}", e),
+                        }
+                    }
+                }
+                IdleState::ConnectedIdle => {
+                    info!("Key activity detected, tightening connection parameters");
+                    power::tighten_conn_params(conn, idle_config);
+                    state = IdleState::Active;
+                }
+                IdleState::Active => {}
+            }
+        }
+
+        // Nothing is connected while `Disconnected`, so there's no GATT writer to send
+        // reports to; just keep wake-scanning below instead.
+        if !matches!(state, IdleState::Disconnected) {
+            keyboard.send_keyboard_report(ble_keyboard_writer).await;
+            keyboard.send_media_report(ble_media_writer).await;
+            keyboard.send_system_control_report(ble_system_control_writer).await;
+            keyboard.send_mouse_report(ble_mouse_writer).await;
+        }
+
+        state = match state {
+            IdleState::Active => {
+                if last_activity.elapsed() >= idle_config.idle_timeout {
+                    info!("Idle timeout reached, relaxing connection parameters");
+                    power::relax_conn_params(conn, idle_config);
+                    IdleState::ConnectedIdle
+                } else {
+                    IdleState::Active
+                }
+            }
+            IdleState::ConnectedIdle => {
+                if last_activity.elapsed() >= idle_config.idle_timeout + idle_config.disconnect_timeout {
+                    info!("Still idle, dropping connection and backing off scan rate");
+                    conn.disconnect();
+                    IdleState::Disconnected
+                } else {
+                    IdleState::ConnectedIdle
+                }
+            }
+            IdleState::Disconnected => {
+                Timer::after(idle_config.wake_scan_interval).await;
+                IdleState::Disconnected
+            }
+        };
     }
 }
 
-/// BLE keyboard task, run the keyboard with the ble server
-pub(crate) async fn ble_battery_task(ble_server: &BleServer, conn: &Connection) {
+/// BLE battery task, periodically samples the battery voltage via SAADC and keeps the
+/// GATT battery characteristic (and any subscribed centrals) up to date.
+///
+/// `saadc` should already be configured by the caller to read the battery's VDD or
+/// divider pin; `config` controls the sample interval and the divider ratio used to
+/// recover the true battery voltage from the pin reading.
+pub(crate) async fn ble_battery_task(
+    ble_server: &BleServer,
+    conn: &Connection,
+    saadc: Saadc<'_, 1>,
+    config: BatteryConfig,
+) {
     // Wait 2 seconds, ensure that gatt server has been started
     Timer::after_secs(2).await;
-    ble_server.set_battery_value(conn, &50);
+
+    let mut monitor = BatteryMonitor::new(saadc, config.divider_ratio).await;
+    notify_battery_value(ble_server, conn, monitor.level());
     loop {
-        // TODO: A real battery service
-        Timer::after_secs(10).await
+        battery::sleep_until_next_sample(&config).await;
+        if let Some(level) = monitor.sample().await {
+            info!("Battery level changed: {}", level);
+            notify_battery_value(ble_server, conn, level);
+        }
+    }
+}
+
+/// Updates the GATTS battery level attribute and explicitly pushes an HVX notification
+/// to `conn`, rather than assuming `set_battery_value` already notifies subscribers -
+/// setting a GATTS attribute's value and notifying it are distinct operations on the
+/// softdevice.
+fn notify_battery_value(ble_server: &BleServer, conn: &Connection, level: u8) {
+    ble_server.set_battery_value(conn, &level);
+    if let Err(e) = ble_server.battery_level_notify(conn, &level) {
+        warn!("Failed to notify battery level: {}", e);
+    }
+}
+
+/// Dongle-side task: connects to every bonded RMK keyboard, then for each link
+/// discovers the HID service and forwards decoded input reports over USB HID to
+/// the host, analogous to how [`keyboard_ble_task`] drives a peripheral's GATT writers.
+#[embassy_executor::task]
+pub(crate) async fn keyboard_ble_central_task(
+    sd: &'static nrf_softdevice::Softdevice,
+    bonds: &'static [Option<bonder::BondInfo>; BONDED_DEVICE_NUM],
+    usb_keyboard_writer: &'static mut dyn HidWriterWrapper,
+    usb_media_writer: &'static mut dyn HidWriterWrapper,
+    usb_system_control_writer: &'static mut dyn HidWriterWrapper,
+    usb_mouse_writer: &'static mut dyn HidWriterWrapper,
+) -> ! {
+    let connections = central::connect_bonded_peripherals(sd, bonds).await;
+
+    loop {
+        for conn in connections.iter() {
+            central::run_central_link(conn, |report| {
+                let _ = match report.report_type {
+                    central::CentralReportType::Keyboard => {
+                        usb_keyboard_writer.write_serialize(&report.data)
+                    }
+                    central::CentralReportType::Media => {
+                        usb_media_writer.write_serialize(&report.data)
+                    }
+                    central::CentralReportType::SystemControl => {
+                        usb_system_control_writer.write_serialize(&report.data)
+                    }
+                    central::CentralReportType::Mouse => {
+                        usb_mouse_writer.write_serialize(&report.data)
+                    }
+                };
+            })
+            .await;
+        }
+        Timer::after_secs(1).await;
+    }
+}
+
+/// Runs the L2CAP config-sync side channel for one connection: accepts a
+/// connection-oriented channel on [`l2cap::CONFIG_SYNC_PSM`] and streams keymap/config
+/// blobs into flash, independent of the small HID feature reports used elsewhere.
+pub(crate) async fn config_sync_task<F: NorFlash>(
+    sd: &'static nrf_softdevice::Softdevice,
+    conn: &Connection,
+    flash: &mut F,
+) {
+    match l2cap::accept_config_channel(sd, conn).await {
+        Ok(channel) => {
+            l2cap::run_config_channel(&channel, flash, |frame| {
+                if frame.len() < 4 {
+                    return l2cap::ConfigMessage::Ignored;
+                }
+                let key = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]);
+                l2cap::ConfigMessage::KeymapBlob {
+                    key,
+                    data: &frame[4..],
+                }
+            })
+            .await;
+        }
+        Err(e) => info!("No config-sync channel accepted: {}", e),
     }
 }
@@ -0,0 +1,68 @@
+use defmt::info;
+use nrf_softdevice::ble::{advertise_connectable, peripheral, Address, AdvertiseError, Connection};
+
+use super::bonder::BondInfo;
+
+/// Connectable advertising payload used by undirected advertising and by extended
+/// directed advertising on `nrf52840_ble`; legacy directed advertising carries none.
+const ADV_DATA: &[u8] = &[
+    0x02, 0x01, raw_flags::BLE_GAP_ADV_FLAGS_LE_ONLY_GENERAL_DISC_MODE as u8,
+];
+
+mod raw_flags {
+    pub(super) use nrf_softdevice::raw::*;
+}
+
+/// Starts directed advertising at the peer recorded in `bond`.
+///
+/// Extended advertising (and thus `ExtendedDirected`) is a Bluetooth 5 / S140 feature
+/// not available on the S132 softdevice used for `nrf52832_ble`, which falls back to
+/// legacy directed advertising instead.
+#[cfg(feature = "nrf52840_ble")]
+pub(crate) async fn start_directed_advertising(bond: &BondInfo) -> Result<Connection, AdvertiseError> {
+    info!("Starting directed advertising");
+    advertise_connectable(
+        unsafe { nrf_softdevice::Softdevice::steal() },
+        peripheral::ConnectableAdvertisement::ExtendedDirected {
+            adv_data: ADV_DATA,
+            peer: bond.peer_address,
+        },
+        &peripheral::Config::default(),
+    )
+    .await
+}
+
+/// Starts directed advertising at the peer recorded in `bond`, using legacy directed
+/// advertising since `nrf52832_ble` runs the S132 softdevice, which has no extended
+/// advertising support.
+#[cfg(feature = "nrf52832_ble")]
+pub(crate) async fn start_directed_advertising(bond: &BondInfo) -> Result<Connection, AdvertiseError> {
+    info!("Starting directed advertising");
+    advertise_connectable(
+        unsafe { nrf_softdevice::Softdevice::steal() },
+        peripheral::ConnectableAdvertisement::Directed {
+            peer: bond.peer_address,
+        },
+        &peripheral::Config::default(),
+    )
+    .await
+}
+
+/// Falls back to ordinary undirected advertising, open to any central.
+pub(crate) async fn start_undirected_advertising() -> Result<Connection, AdvertiseError> {
+    info!("Starting undirected advertising");
+    advertise_connectable(
+        unsafe { nrf_softdevice::Softdevice::steal() },
+        peripheral::ConnectableAdvertisement::ScannableUndirected {
+            adv_data: ADV_DATA,
+            scan_data: &[],
+        },
+        &peripheral::Config::default(),
+    )
+    .await
+}
+
+/// Builds a scan/connect whitelist from every bonded address across all slots.
+pub(crate) fn whitelist_from_bonds<const N: usize>(bonds: &[Option<BondInfo>; N]) -> heapless::Vec<Address, N> {
+    bonds.iter().filter_map(|b| b.as_ref().map(|b| b.peer_address)).collect()
+}
@@ -0,0 +1,142 @@
+use embassy_nrf::saadc::Saadc;
+use embassy_time::{Duration, Timer};
+
+/// Default LiPo discharge curve: (millivolts, percent) pairs sorted by ascending voltage.
+const DEFAULT_VOLTAGE_CURVE: &[(u16, u8)] = &[
+    (3300, 0),
+    (3500, 10),
+    (3600, 20),
+    (3650, 30),
+    (3700, 40),
+    (3750, 50),
+    (3800, 60),
+    (3850, 70),
+    (3900, 80),
+    (4000, 90),
+    (4200, 100),
+];
+
+/// Converts a battery voltage (mV) to a 0-100% level, clamping at the curve's endpoints.
+fn voltage_to_percent(millivolts: u16, curve: &[(u16, u8)]) -> u8 {
+    if let Some(&(_, p)) = curve.first().filter(|&&(v, _)| millivolts <= v) {
+        return p;
+    }
+    if let Some(&(_, p)) = curve.last().filter(|&&(v, _)| millivolts >= v) {
+        return p;
+    }
+
+    for w in curve.windows(2) {
+        let (v_lo, p_lo) = w[0];
+        let (v_hi, p_hi) = w[1];
+        if millivolts >= v_lo && millivolts <= v_hi {
+            let span = (v_hi - v_lo) as u32;
+            let offset = (millivolts - v_lo) as u32;
+            let p_span = (p_hi - p_lo) as u32;
+            return p_lo + ((offset * p_span) / span) as u8;
+        }
+    }
+
+    // Unreachable given the first/last checks above, but fall back to 0 defensively.
+    0
+}
+
+/// Samples the battery voltage via the nRF SAADC and tracks a smoothed 0-100% level.
+pub(crate) struct BatteryMonitor<'a> {
+    saadc: Saadc<'a, 1>,
+    /// Ratio of actual battery voltage to the voltage presented at the ADC pin,
+    /// e.g. `2.0` for a 1:1 resistor divider.
+    divider_ratio: f32,
+    /// Current smoothed level, updated in place by [`BatteryMonitor::sample`].
+    level: u8,
+}
+
+impl<'a> BatteryMonitor<'a> {
+    /// Creates a new monitor, taking the initial level from a single blocking sample.
+    pub(crate) async fn new(mut saadc: Saadc<'a, 1>, divider_ratio: f32) -> Self {
+        let mut buf = [0i16; 1];
+        saadc.sample(&mut buf).await;
+        let level = voltage_to_percent(
+            Self::raw_to_millivolts(buf[0], divider_ratio),
+            DEFAULT_VOLTAGE_CURVE,
+        );
+        Self {
+            saadc,
+            divider_ratio,
+            level,
+        }
+    }
+
+    fn raw_to_millivolts(raw: i16, divider_ratio: f32) -> u16 {
+        // SAADC is configured for 0..=3600mV full scale over 12 bits by the caller.
+        let pin_mv = (raw.max(0) as u32 * 3600) / 4096;
+        (pin_mv as f32 * divider_ratio) as u16
+    }
+
+    /// Samples and low-pass filters (`level = level*7/8 + sample/8`); `Some` only when
+    /// the smoothed level changed.
+    pub(crate) async fn sample(&mut self) -> Option<u8> {
+        let mut buf = [0i16; 1];
+        self.saadc.sample(&mut buf).await;
+        let sample_percent = voltage_to_percent(
+            Self::raw_to_millivolts(buf[0], self.divider_ratio),
+            DEFAULT_VOLTAGE_CURVE,
+        );
+
+        let smoothed = ((self.level as u32 * 7 + sample_percent as u32) / 8) as u8;
+        if smoothed == self.level {
+            None
+        } else {
+            self.level = smoothed;
+            Some(self.level)
+        }
+    }
+
+    pub(crate) fn level(&self) -> u8 {
+        self.level
+    }
+}
+
+/// How often the battery is resampled, and how the SAADC pin voltage relates to
+/// true battery voltage.
+pub(crate) struct BatteryConfig {
+    pub(crate) sample_interval: Duration,
+    /// Ratio of actual battery voltage to the voltage presented at the ADC pin.
+    pub(crate) divider_ratio: f32,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval: Duration::from_secs(60),
+            divider_ratio: 2.0,
+        }
+    }
+}
+
+pub(crate) async fn sleep_until_next_sample(config: &BatteryConfig) {
+    Timer::after(config.sample_interval).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_below_and_above_the_curve() {
+        assert_eq!(voltage_to_percent(3000, DEFAULT_VOLTAGE_CURVE), 0);
+        assert_eq!(voltage_to_percent(4500, DEFAULT_VOLTAGE_CURVE), 100);
+    }
+
+    #[test]
+    fn interpolates_between_curve_points() {
+        // Halfway between (3300, 0) and (3500, 10) should read ~5%.
+        assert_eq!(voltage_to_percent(3400, DEFAULT_VOLTAGE_CURVE), 5);
+    }
+
+    #[test]
+    fn matches_curve_points_exactly() {
+        for &(mv, pct) in DEFAULT_VOLTAGE_CURVE {
+            assert_eq!(voltage_to_percent(mv, DEFAULT_VOLTAGE_CURVE), pct);
+        }
+    }
+}
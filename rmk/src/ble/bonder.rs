@@ -0,0 +1,45 @@
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use nrf_softdevice::ble::{security::SecurityHandler, Address};
+
+use super::BONDED_DEVICE_NUM;
+
+/// One saved bond, keyed by slot number in flash (`0..BONDED_DEVICE_NUM`).
+#[derive(Clone, Copy, defmt::Format)]
+pub(crate) struct BondInfo {
+    pub(crate) slot_num: u8,
+    pub(crate) peer_address: Address,
+}
+
+/// Messages sent from the BLE task(s) to [`super::flash_task`] to persist bonding state.
+#[derive(defmt::Format)]
+pub(crate) enum FlashOperationMessage {
+    /// Store or update the bond in the given slot.
+    BondInfo(BondInfo),
+    /// Erase the bond stored in the given slot.
+    Clear(u8),
+    /// Record which bonded slot is currently active, so it can be restored on boot.
+    ActiveSlot(u8),
+}
+
+/// Channel used by BLE tasks to hand off flash writes to [`super::flash_task`].
+pub(crate) static FLASH_CHANNEL: Channel<ThreadModeRawMutex, FlashOperationMessage, 4> =
+    Channel::new();
+
+/// Bonds currently known to this device, indexed by slot number.
+pub(crate) struct Bonder {
+    bonds: [Option<BondInfo>; BONDED_DEVICE_NUM],
+}
+
+impl Bonder {
+    pub(crate) fn new() -> Self {
+        Self {
+            bonds: [None; BONDED_DEVICE_NUM],
+        }
+    }
+
+    pub(crate) fn bonds(&self) -> &[Option<BondInfo>; BONDED_DEVICE_NUM] {
+        &self.bonds
+    }
+}
+
+impl SecurityHandler for Bonder {}
@@ -0,0 +1,194 @@
+use defmt::{info, warn};
+use nrf_softdevice::ble::{central, gatt_client, Address};
+
+use super::bonder::BondInfo;
+use crate::ble::BONDED_DEVICE_NUM;
+
+/// GATT client for an RMK peripheral's HID service. One characteristic per input
+/// report, mirroring the peripheral's four `BleXxxWriter`s (keyboard/media/system
+/// control/mouse) instead of a single generic report blob.
+///
+/// All four share UUID `0x2a4d` (Report), so which field `gatt_client::discover` binds
+/// to which physical characteristic is not guaranteed to follow declaration order; each
+/// carries its own Report Reference descriptor (`0x2a4b`) to disambiguate, read by
+/// [`read_report_mapping`] after discovery.
+#[nrf_softdevice::gatt_client(uuid = "1812")]
+pub(crate) struct HidServiceClient {
+    #[characteristic(uuid = "2a4d", read, notify)]
+    #[descriptor(uuid = "2a4b")]
+    keyboard_report: [u8; 8],
+    #[characteristic(uuid = "2a4d", read, notify)]
+    #[descriptor(uuid = "2a4b")]
+    media_report: [u8; 2],
+    #[characteristic(uuid = "2a4d", read, notify)]
+    #[descriptor(uuid = "2a4b")]
+    system_control_report: [u8; 1],
+    #[characteristic(uuid = "2a4d", read, notify)]
+    #[descriptor(uuid = "2a4b")]
+    mouse_report: [u8; 5],
+}
+
+/// Which of the HID service's input-report characteristics a [`CentralReport`] came
+/// from.
+#[derive(Clone, Copy)]
+pub(crate) enum CentralReportType {
+    Keyboard,
+    Media,
+    SystemControl,
+    Mouse,
+}
+
+/// Report IDs the peripheral's `hid_service`/`descriptor` module assigns to each input
+/// report, matching the Report Reference descriptor (`0x2a4b`) bound to each
+/// characteristic: byte 0 is the report ID, byte 1 is the report type (1 = Input).
+const REPORT_ID_KEYBOARD: u8 = 1;
+const REPORT_ID_MEDIA: u8 = 2;
+const REPORT_ID_SYSTEM_CONTROL: u8 = 3;
+const REPORT_ID_MOUSE: u8 = 4;
+
+fn report_type_from_id(report_id: u8) -> Option<CentralReportType> {
+    match report_id {
+        REPORT_ID_KEYBOARD => Some(CentralReportType::Keyboard),
+        REPORT_ID_MEDIA => Some(CentralReportType::Media),
+        REPORT_ID_SYSTEM_CONTROL => Some(CentralReportType::SystemControl),
+        REPORT_ID_MOUSE => Some(CentralReportType::Mouse),
+        _ => None,
+    }
+}
+
+/// Which [`CentralReportType`] each declared field of [`HidServiceClient`] actually
+/// carries, determined from each characteristic's Report Reference descriptor rather
+/// than assumed from field declaration order.
+struct ReportMapping {
+    keyboard_field: Option<CentralReportType>,
+    media_field: Option<CentralReportType>,
+    system_control_field: Option<CentralReportType>,
+    mouse_field: Option<CentralReportType>,
+}
+
+/// Reads the Report Reference descriptor bound to each of `client`'s four report
+/// characteristics and maps them to a [`CentralReportType`] by report ID.
+async fn read_report_mapping(
+    conn: &nrf_softdevice::ble::Connection,
+    client: &HidServiceClient,
+) -> ReportMapping {
+    async fn read_kind(
+        conn: &nrf_softdevice::ble::Connection,
+        handle: u16,
+    ) -> Option<CentralReportType> {
+        let mut buf = [0_u8; 2];
+        match gatt_client::read(conn, handle, &mut buf).await {
+            Ok(_) => report_type_from_id(buf[0]),
+            Err(e) => {
+                warn!("Failed to read Report Reference descriptor: {}", e);
+                None
+            }
+        }
+    }
+
+    ReportMapping {
+        keyboard_field: read_kind(conn, client.keyboard_report_report_reference_handle).await,
+        media_field: read_kind(conn, client.media_report_report_reference_handle).await,
+        system_control_field: read_kind(conn, client.system_control_report_report_reference_handle)
+            .await,
+        mouse_field: read_kind(conn, client.mouse_report_report_reference_handle).await,
+    }
+}
+
+/// One decoded HID input report forwarded from a bonded peripheral to the host.
+pub(crate) struct CentralReport {
+    pub(crate) report_type: CentralReportType,
+    pub(crate) data: heapless::Vec<u8, 8>,
+}
+
+/// Connects to every bonded RMK keyboard address recorded in flash, whitelisted the
+/// same way `ble_bas_central` does.
+pub(crate) async fn connect_bonded_peripherals(
+    sd: &'static nrf_softdevice::Softdevice,
+    bonds: &[Option<BondInfo>; BONDED_DEVICE_NUM],
+) -> heapless::Vec<nrf_softdevice::ble::Connection, BONDED_DEVICE_NUM> {
+    let mut connections = heapless::Vec::new();
+    let whitelist: heapless::Vec<Address, BONDED_DEVICE_NUM> = bonds
+        .iter()
+        .filter_map(|b| b.as_ref().map(|b| b.peer_address))
+        .collect();
+
+    for addr in whitelist {
+        let config = central::ConnectConfig {
+            scan_config: central::ScanConfig {
+                whitelist: Some(&[addr]),
+                ..Default::default()
+            },
+            conn_params: Default::default(),
+        };
+
+        match central::connect(sd, &config).await {
+            Ok(conn) => {
+                info!("Connected to bonded keyboard");
+                if connections.push(conn).is_err() {
+                    warn!("Dongle connection slots exhausted");
+                    break;
+                }
+            }
+            Err(e) => warn!("Failed to connect to bonded keyboard: {}", e),
+        }
+    }
+
+    connections
+}
+
+/// Discovers the HID service on a connected peripheral, subscribes to all four input
+/// report characteristics, and forwards every decoded report to `report_sender`.
+pub(crate) async fn run_central_link(
+    conn: &nrf_softdevice::ble::Connection,
+    mut report_sender: impl FnMut(CentralReport),
+) {
+    let client: HidServiceClient = match gatt_client::discover(conn).await {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("HID service discovery failed: {}", e);
+            return;
+        }
+    };
+
+    let _ = gatt_client::set_notifications(conn, client.keyboard_report_cccd_handle, true).await;
+    let _ = gatt_client::set_notifications(conn, client.media_report_cccd_handle, true).await;
+    let _ =
+        gatt_client::set_notifications(conn, client.system_control_report_cccd_handle, true).await;
+    let _ = gatt_client::set_notifications(conn, client.mouse_report_cccd_handle, true).await;
+
+    let mapping = read_report_mapping(conn, &client).await;
+
+    gatt_client::run(conn, &client, |event| {
+        let (declared_type, data) = match event {
+            HidServiceClientEvent::KeyboardReportNotification(data) => {
+                (&mapping.keyboard_field, &data[..])
+            }
+            HidServiceClientEvent::MediaReportNotification(data) => {
+                (&mapping.media_field, &data[..])
+            }
+            HidServiceClientEvent::SystemControlReportNotification(data) => {
+                (&mapping.system_control_field, &data[..])
+            }
+            HidServiceClientEvent::MouseReportNotification(data) => {
+                (&mapping.mouse_field, &data[..])
+            }
+        };
+
+        // `declared_type` comes from the Report Reference descriptor read above, not
+        // from which field `gatt_client::discover` happened to bind this handle to.
+        let Some(report_type) = declared_type else {
+            warn!("Dropping notification with unrecognized Report Reference");
+            return;
+        };
+
+        if let Ok(data) = heapless::Vec::from_slice(data) {
+            report_sender(CentralReport {
+                report_type: *report_type,
+                data,
+            });
+        }
+    })
+    .await
+    .ok();
+}
@@ -0,0 +1,201 @@
+use defmt::{info, warn};
+use embedded_storage::nor_flash::NorFlash;
+use nrf_softdevice::{
+    ble::{l2cap, Connection},
+    Softdevice,
+};
+use sequential_storage::{cache::NoCache, map::store_item};
+
+/// PSM for the RMK config-sync L2CAP connection-oriented channel.
+pub(crate) const CONFIG_SYNC_PSM: u16 = 0x0080;
+
+/// MTU for one SDU on the config-sync channel.
+const L2CAP_MTU: usize = 128;
+
+/// Largest reassembled frame we'll accept; bounds `FrameReassembler`'s internal buffer.
+const MAX_FRAME_LEN: usize = 4096;
+
+/// Flash range keymap/config blobs are persisted to, kept separate from the bonding
+/// info range.
+#[cfg(feature = "nrf52840_ble")]
+const KEYMAP_FLASH_RANGE: core::ops::Range<u32> = 0x82000..0x90000;
+#[cfg(feature = "nrf52832_ble")]
+const KEYMAP_FLASH_RANGE: core::ops::Range<u32> = 0x74000..0x7E000;
+
+/// Registers the config-sync PSM and accepts a connection-oriented channel on `conn`.
+pub(crate) async fn accept_config_channel(
+    sd: &'static Softdevice,
+    conn: &Connection,
+) -> Result<l2cap::L2cap, l2cap::AcceptError> {
+    let config = l2cap::Config {
+        // Replenished as the receive buffer drains, so a slow flash write throttles the
+        // peer instead of dropping SDUs.
+        credits: 8,
+        mtu: L2CAP_MTU as u16,
+        ..Default::default()
+    };
+    l2cap::L2cap::setup(sd, CONFIG_SYNC_PSM)?;
+    l2cap::L2cap::accept(sd, conn, &config).await
+}
+
+/// Reassembles length-prefixed frames out of a stream of fixed-size SDUs.
+///
+/// A frame is a 2-byte little-endian length prefix followed by that many bytes of
+/// payload, which may itself be split across several SDUs. The declared length is
+/// validated against the internal buffer before any bytes are copied, so an oversized or
+/// malformed prefix just drops the in-flight frame instead of overflowing the buffer.
+struct FrameReassembler {
+    buf: [u8; MAX_FRAME_LEN],
+    len: usize,
+    expected: Option<usize>,
+}
+
+impl FrameReassembler {
+    fn new() -> Self {
+        Self {
+            buf: [0; MAX_FRAME_LEN],
+            len: 0,
+            expected: None,
+        }
+    }
+
+    /// Feeds one SDU's worth of bytes in, calling `on_frame` for each complete frame.
+    fn push(&mut self, mut sdu: &[u8], mut on_frame: impl FnMut(&[u8])) {
+        while !sdu.is_empty() {
+            let expected = match self.expected {
+                Some(expected) => expected,
+                None => {
+                    if sdu.len() < 2 {
+                        warn!("Dropped malformed length-prefixed frame header");
+                        return;
+                    }
+                    let declared = u16::from_le_bytes([sdu[0], sdu[1]]) as usize;
+                    sdu = &sdu[2..];
+                    if declared > self.buf.len() {
+                        warn!("Dropped oversized frame ({} > {} bytes)", declared, self.buf.len());
+                    }
+                    self.len = 0;
+                    self.expected = Some(declared);
+                    declared
+                }
+            };
+
+            // An oversized frame's payload is counted and skipped, never buffered.
+            let oversized = expected > self.buf.len();
+            let take = core::cmp::min(expected - self.len, sdu.len());
+            if !oversized {
+                self.buf[self.len..self.len + take].copy_from_slice(&sdu[..take]);
+            }
+            self.len += take;
+            sdu = &sdu[take..];
+
+            if self.len == expected {
+                if !oversized {
+                    on_frame(&self.buf[..self.len]);
+                }
+                self.expected = None;
+            }
+        }
+    }
+}
+
+/// Reads SDUs off `channel`, reassembles them into frames, and dispatches each complete
+/// frame to `handler`. Runs until the channel closes.
+pub(crate) async fn run_config_channel<F: NorFlash>(
+    channel: &l2cap::L2cap,
+    flash: &mut F,
+    mut handler: impl FnMut(&[u8]) -> ConfigMessage,
+) {
+    let mut reassembler = FrameReassembler::new();
+
+    loop {
+        let mut sdu = [0_u8; L2CAP_MTU];
+        let n = match channel.rx(&mut sdu).await {
+            Ok(n) => n,
+            Err(e) => {
+                warn!("L2CAP channel closed: {}", e);
+                return;
+            }
+        };
+
+        let mut message = None;
+        reassembler.push(&sdu[..n], |frame| message = Some(handler(frame)));
+
+        match message {
+            Some(ConfigMessage::KeymapBlob { key, data }) => {
+                store_keymap_blob(flash, key, data).await;
+            }
+            Some(ConfigMessage::Ignored) | None => {}
+        }
+    }
+}
+
+/// One decoded config-sync message, handed back by the caller-supplied frame handler.
+pub(crate) enum ConfigMessage<'a> {
+    /// A keymap/config blob to persist under `key`.
+    KeymapBlob { key: u32, data: &'a [u8] },
+    /// A frame the handler didn't recognize.
+    Ignored,
+}
+
+/// Largest keymap/config blob we'll persist in one write.
+const MAX_BLOB_LEN: usize = 4096;
+
+async fn store_keymap_blob<F: NorFlash>(flash: &mut F, key: u32, data: &[u8]) {
+    if data.len() > MAX_BLOB_LEN {
+        warn!(
+            "Rejected keymap blob for key {}: {} bytes exceeds max {}",
+            key,
+            data.len(),
+            MAX_BLOB_LEN
+        );
+        return;
+    }
+
+    info!("Storing keymap blob for key {}, {} bytes", key, data.len());
+    let mut buffer = [0_u8; MAX_BLOB_LEN];
+    buffer[..data.len()].copy_from_slice(data);
+    store_item::<u32, _>(
+        flash,
+        KEYMAP_FLASH_RANGE,
+        NoCache::new(),
+        &mut buffer,
+        &key,
+        &&buffer[..data.len()],
+    )
+    .await
+    .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reassembles_a_frame_split_across_sdus() {
+        let mut reassembler = FrameReassembler::new();
+        let mut frames: heapless::Vec<heapless::Vec<u8, 16>, 4> = heapless::Vec::new();
+
+        reassembler.push(&[4, 0, b'a', b'b'], |f| {
+            frames.push(heapless::Vec::from_slice(f).unwrap()).ok();
+        });
+        reassembler.push(&[b'c', b'd'], |f| {
+            frames.push(heapless::Vec::from_slice(f).unwrap()).ok();
+        });
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].as_slice(), b"abcd");
+    }
+
+    #[test]
+    fn drops_frame_declared_larger_than_the_buffer() {
+        let mut reassembler = FrameReassembler::new();
+        let mut called = false;
+
+        let oversized_len = (MAX_FRAME_LEN + 1) as u16;
+        let header = oversized_len.to_le_bytes();
+        reassembler.push(&[header[0], header[1], 1, 2, 3], |_| called = true);
+
+        assert!(!called);
+    }
+}
@@ -0,0 +1,61 @@
+use defmt::warn;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
+use embassy_time::Duration;
+use nrf_softdevice::{ble::Connection, raw};
+
+/// Signalled by `keyboard_ble_task` on every detected key transition.
+pub(crate) static ACTIVITY_SIGNAL: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// Tunables for `keyboard_ble_task`'s idle power-management state machine.
+pub(crate) struct IdlePowerConfig {
+    /// How long without key activity before connection parameters relax.
+    pub(crate) idle_timeout: Duration,
+    /// How much longer after that before the connection is dropped entirely.
+    pub(crate) disconnect_timeout: Duration,
+    /// Matrix scan interval used once the idle connection has been dropped, instead of
+    /// scanning every loop iteration.
+    pub(crate) wake_scan_interval: Duration,
+    /// Connection parameters requested once the link has been idle for a while:
+    /// higher slave latency and a longer interval, so the radio wakes up less often.
+    pub(crate) idle_conn_params: raw::ble_gap_conn_params_t,
+    /// Connection parameters requested as soon as activity resumes.
+    pub(crate) active_conn_params: raw::ble_gap_conn_params_t,
+}
+
+impl Default for IdlePowerConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(30),
+            disconnect_timeout: Duration::from_secs(60),
+            wake_scan_interval: Duration::from_millis(200),
+            idle_conn_params: raw::ble_gap_conn_params_t {
+                min_conn_interval: 400, // 500ms, in 1.25ms units
+                max_conn_interval: 400,
+                slave_latency: 30,
+                // Must exceed 2 * (1 + slave_latency) * max_conn_interval (31s here), in
+                // 10ms units; 3200 (32s) is the spec's own ceiling for this field.
+                conn_sup_timeout: 3200,
+            },
+            active_conn_params: raw::ble_gap_conn_params_t {
+                min_conn_interval: 12, // 15ms
+                max_conn_interval: 12,
+                slave_latency: 0,
+                conn_sup_timeout: 600,
+            },
+        }
+    }
+}
+
+/// Requests relaxed connection parameters (higher slave latency, longer interval).
+pub(crate) fn relax_conn_params(conn: &Connection, config: &IdlePowerConfig) {
+    if let Err(e) = conn.set_conn_params(config.idle_conn_params) {
+        warn!("Failed to relax connection parameters: {}", e);
+    }
+}
+
+/// Requests full-rate connection parameters.
+pub(crate) fn tighten_conn_params(conn: &Connection, config: &IdlePowerConfig) {
+    if let Err(e) = conn.set_conn_params(config.active_conn_params) {
+        warn!("Failed to tighten connection parameters: {}", e);
+    }
+}